@@ -1,10 +1,115 @@
-use tauri::Manager;
+use log::{error, info};
+use tauri::{AppHandle, Emitter, Manager};
 use tauri_plugin_shell::ShellExt;
+use std::path::PathBuf;
 use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-// Store the sidecar process handle to manage its lifecycle
+const MAX_RESTART_ATTEMPTS: u32 = 5;
+
+// A single log record, re-emitted to the webview so it can render a live log viewer
+#[derive(Clone, serde::Serialize)]
+struct AppLogRecord {
+    level: String,
+    target: String,
+    message: String,
+    ts: u64,
+}
+
+// Install a `log`/`fern` dispatcher that writes leveled logs to stdout, to a
+// `ctrldeck.log` file in the app's log directory and to the webview, unifying
+// host-side and sidecar-side logging under one subsystem. The log file is
+// appended to across runs; it is not rotated.
+fn init_logging(app: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
+    let log_dir = app.path().app_log_dir()?;
+    std::fs::create_dir_all(&log_dir)?;
+
+    let event_handle = app.clone();
+    fern::Dispatch::new()
+        .format(|out, message, record| {
+            out.finish(format_args!("[{} {}] {}", record.level(), record.target(), message))
+        })
+        .level(log::LevelFilter::Info)
+        .chain(std::io::stdout())
+        .chain(fern::log_file(log_dir.join("ctrldeck.log"))?)
+        .chain(fern::Output::call(move |record| {
+            // Sidecar lines (target "backend") are already forwarded to the
+            // webview as `backend-log` events; re-emitting them here too would
+            // show every backend line twice in a unified log viewer.
+            if record.target() == "backend" {
+                return;
+            }
+            let _ = event_handle.emit(
+                "app-log",
+                AppLogRecord {
+                    level: record.level().to_string(),
+                    target: record.target().to_string(),
+                    message: record.args().to_string(),
+                    ts: now_ts(),
+                },
+            );
+        }))
+        .apply()?;
+
+    Ok(())
+}
+
+// Line the sidecar prints on stdout once it's actually accepting requests.
+// Update this if the server's startup banner changes.
+const SERVER_READY_MARKER: &str = "Server ready";
+
+// A startup checkpoint, emitted so the UI can show a progress/splash screen
+// instead of racing the sidecar before it's actually ready.
+#[derive(Clone, serde::Serialize)]
+struct SetupProgress {
+    stage: String,
+    message: String,
+}
+
+fn emit_progress(app: &AppHandle, stage: &str, message: impl Into<String>) {
+    let _ = app.emit(
+        "setup-progress",
+        SetupProgress {
+            stage: stage.into(),
+            message: message.into(),
+        },
+    );
+}
+
+// A single line of sidecar output, forwarded to the webview for live display
+#[derive(Clone, serde::Serialize)]
+struct BackendLog {
+    level: String,
+    line: String,
+    ts: u64,
+}
+
+fn now_ts() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// Current lifecycle state of the sidecar process
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+enum ServerStatus {
+    Stopped,
+    Starting,
+    Running,
+    Crashed,
+}
+
+// Store the sidecar process handle and its lifecycle state.
+// `generation` is bumped on every spawn and on every intentional stop/restart,
+// so the watch task spawned for an older child can tell its process's
+// `Terminated` event was caused by an intentional stop/restart rather than a
+// crash, and skip self-healing for it.
 struct SidecarState {
     child: Mutex<Option<tauri_plugin_shell::process::CommandChild>>,
+    status: Mutex<ServerStatus>,
+    generation: Mutex<u64>,
 }
 
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
@@ -13,6 +118,257 @@ fn greet(name: &str) -> String {
     format!("Hello, {}! You've been greeted from Rust!", name)
 }
 
+#[tauri::command]
+fn server_status(state: tauri::State<SidecarState>) -> ServerStatus {
+    *state.status.lock().unwrap()
+}
+
+#[tauri::command]
+fn start_server(app: AppHandle) -> Result<(), String> {
+    let state = app.state::<SidecarState>();
+    {
+        // Check-and-set under a single lock so two concurrent calls can't
+        // both observe "not running" and both spawn a sidecar.
+        let mut status = state.status.lock().unwrap();
+        if matches!(*status, ServerStatus::Starting | ServerStatus::Running) {
+            return Err("Backend server is already running".into());
+        }
+        *status = ServerStatus::Starting;
+    }
+    spawn_sidecar(app.clone(), 0)
+}
+
+#[tauri::command]
+fn stop_server(state: tauri::State<SidecarState>) -> Result<(), String> {
+    let child = state.child.lock().unwrap().take();
+    match child {
+        Some(child) => {
+            // Bump the generation before killing so the watch task sees this
+            // termination as intentional and doesn't try to self-heal it.
+            *state.generation.lock().unwrap() += 1;
+            child.kill().map_err(|e| e.to_string())?;
+            *state.status.lock().unwrap() = ServerStatus::Stopped;
+            info!("Backend server stopped");
+            Ok(())
+        }
+        None => Err("Backend server is not running".into()),
+    }
+}
+
+#[tauri::command]
+fn send_to_backend(line: String, state: tauri::State<SidecarState>) -> Result<(), String> {
+    let mut guard = state.child.lock().unwrap();
+    let child = guard.as_mut().ok_or("Backend server is not running")?;
+    let mut data = line.into_bytes();
+    data.push(b'\n');
+    child.write(&data).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn restart_server(app: AppHandle) -> Result<(), String> {
+    let state = app.state::<SidecarState>();
+    if let Some(child) = state.child.lock().unwrap().take() {
+        // Bump the generation before killing so the old watch task doesn't
+        // race the fresh spawn below with a duplicate auto-restart.
+        *state.generation.lock().unwrap() += 1;
+        let _ = child.kill();
+    }
+    spawn_sidecar(app.clone(), 0)
+}
+
+const SERVER_ENV_OVERRIDE: &str = "CTRLDECK_SERVER";
+const SERVER_CONFIG_FILE: &str = "config.json";
+const SERVER_CONFIG_KEY: &str = "serverPath";
+
+// An explicit path to launch instead of the bundled sidecar, along with where
+// it came from (used for the `setup-progress` failure message).
+struct ServerOverride {
+    source: String,
+    path: PathBuf,
+}
+
+// Honor an explicit override, checked in priority order: the `CTRLDECK_SERVER`
+// environment variable, then a `serverPath` key in the app's `config.json`.
+fn resolve_server_override(app: &AppHandle) -> Option<ServerOverride> {
+    if let Ok(path) = std::env::var(SERVER_ENV_OVERRIDE) {
+        return Some(ServerOverride {
+            source: format!("${} environment variable", SERVER_ENV_OVERRIDE),
+            path: PathBuf::from(path),
+        });
+    }
+
+    let config_path = app.path().app_config_dir().ok()?.join(SERVER_CONFIG_FILE);
+    let raw = std::fs::read_to_string(config_path).ok()?;
+    let value: serde_json::Value = serde_json::from_str(&raw).ok()?;
+    let path = value.get(SERVER_CONFIG_KEY)?.as_str()?;
+    Some(ServerOverride {
+        source: format!("\"{}\" in {}", SERVER_CONFIG_KEY, SERVER_CONFIG_FILE),
+        path: PathBuf::from(path),
+    })
+}
+
+// Spawn the backend sidecar and wire up its output/termination handling.
+// `attempt` tracks how many consecutive auto-restarts have happened so far,
+// so the exponential backoff and retry cap survive across respawns.
+fn spawn_sidecar(app: AppHandle, attempt: u32) -> Result<(), String> {
+    let state = app.state::<SidecarState>();
+    *state.status.lock().unwrap() = ServerStatus::Starting;
+    // This spawn now owns `child`; bump the generation so any watch task left
+    // over from a previous child treats its eventual `Terminated` as stale.
+    let generation = {
+        let mut g = state.generation.lock().unwrap();
+        *g += 1;
+        *g
+    };
+    emit_progress(&app, "spawning", "Starting backend server...");
+
+    let shell = app.shell();
+    let mut tried = Vec::new();
+    let mut spawned = None;
+
+    // 1. An explicit override (env var or app config setting). Actually try to
+    // spawn it rather than just checking for file existence, so a bad path
+    // falls through to the next source instead of failing outright.
+    if let Some(ServerOverride { source, path }) = resolve_server_override(&app) {
+        if path.is_file() {
+            match shell.command(path.to_string_lossy().to_string()).spawn() {
+                Ok(s) => spawned = Some(s),
+                Err(e) => tried.push(format!("{} ({}): spawn failed: {}", source, path.display(), e)),
+            }
+        } else {
+            tried.push(format!("{} ({}): no such file", source, path.display()));
+        }
+    }
+
+    // 2. The bundled sidecar. `shell.sidecar()` only checks that the binary is
+    // declared in tauri.conf.json's external-bin list, not that it exists on
+    // disk, so the actual spawn is what tells us whether it's really there.
+    if spawned.is_none() {
+        match shell.sidecar("streamdeck-server") {
+            Ok(command) => match command.spawn() {
+                Ok(s) => spawned = Some(s),
+                Err(e) => tried.push(format!("bundled sidecar \"streamdeck-server\": spawn failed: {}", e)),
+            },
+            Err(e) => tried.push(format!("bundled sidecar \"streamdeck-server\": {}", e)),
+        }
+    }
+
+    // 3. A `PATH` lookup.
+    if spawned.is_none() {
+        match which::which("streamdeck-server") {
+            Ok(path) => match shell.command(path.to_string_lossy().to_string()).spawn() {
+                Ok(s) => spawned = Some(s),
+                Err(e) => tried.push(format!("`streamdeck-server` on PATH ({}): spawn failed: {}", path.display(), e)),
+            },
+            Err(e) => tried.push(format!("`streamdeck-server` on PATH: {}", e)),
+        }
+    }
+
+    let (mut rx, child) = spawned.ok_or_else(|| {
+        let msg = format!(
+            "Could not locate the streamdeck-server binary. Tried: {}",
+            tried.join("; ")
+        );
+        *state.status.lock().unwrap() = ServerStatus::Stopped;
+        emit_progress(&app, "failed", msg.clone());
+        msg
+    })?;
+
+    *state.child.lock().unwrap() = Some(child);
+    info!("Backend server started successfully");
+    emit_progress(&app, "waiting", "Waiting for backend server to become ready...");
+
+    let app_handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        use tauri_plugin_shell::process::CommandEvent;
+        let mut ready = false;
+        while let Some(event) = rx.recv().await {
+            if *app_handle.state::<SidecarState>().generation.lock().unwrap() != generation {
+                // A newer spawn (or an explicit stop) has already taken over
+                // `SidecarState`; stop watching this superseded child.
+                break;
+            }
+            match event {
+                CommandEvent::Stdout(line) => {
+                    let line = String::from_utf8_lossy(&line).to_string();
+                    info!(target: "backend", "{}", line);
+                    let _ = app_handle.emit(
+                        "backend-log",
+                        BackendLog {
+                            level: "info".into(),
+                            line: line.clone(),
+                            ts: now_ts(),
+                        },
+                    );
+                    if !ready && line.contains(SERVER_READY_MARKER) {
+                        ready = true;
+                        *app_handle.state::<SidecarState>().status.lock().unwrap() = ServerStatus::Running;
+                        emit_progress(&app_handle, "ready", "Backend server is ready");
+                    }
+                }
+                CommandEvent::Stderr(line) => {
+                    let line = String::from_utf8_lossy(&line).to_string();
+                    error!(target: "backend", "{}", line);
+                    let _ = app_handle.emit(
+                        "backend-log",
+                        BackendLog {
+                            level: "error".into(),
+                            line,
+                            ts: now_ts(),
+                        },
+                    );
+                }
+                CommandEvent::Terminated(payload) => {
+                    info!("Backend process terminated with code: {:?}", payload.code);
+                    let state = app_handle.state::<SidecarState>();
+                    *state.child.lock().unwrap() = None;
+                    *state.status.lock().unwrap() = ServerStatus::Crashed;
+                    if !ready {
+                        emit_progress(
+                            &app_handle,
+                            "failed",
+                            format!("Backend server exited before becoming ready (code {:?})", payload.code),
+                        );
+                    }
+
+                    if attempt < MAX_RESTART_ATTEMPTS {
+                        let delay = Duration::from_secs(1 << attempt.min(3)); // 1s, 2s, 4s, 8s, 8s...
+                        error!(
+                            "Restarting backend server in {:?} (attempt {}/{})",
+                            delay,
+                            attempt + 1,
+                            MAX_RESTART_ATTEMPTS
+                        );
+                        let retry_handle = app_handle.clone();
+                        tauri::async_runtime::spawn(async move {
+                            tokio::time::sleep(delay).await;
+                            // A manual start/restart may have already spawned a
+                            // new child while this backoff was pending; don't
+                            // clobber it with a second, untracked sidecar.
+                            if *retry_handle.state::<SidecarState>().generation.lock().unwrap() != generation {
+                                info!(
+                                    "Skipping scheduled auto-restart; sidecar generation {} was superseded",
+                                    generation
+                                );
+                                return;
+                            }
+                            if let Err(e) = spawn_sidecar(retry_handle, attempt + 1) {
+                                error!("Auto-restart failed: {}", e);
+                            }
+                        });
+                    } else {
+                        error!("Giving up after {} backend restart attempts", MAX_RESTART_ATTEMPTS);
+                    }
+                    break;
+                }
+                _ => {}
+            }
+        }
+    });
+
+    Ok(())
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
@@ -20,67 +376,43 @@ pub fn run() {
         .plugin(tauri_plugin_shell::init())
         .manage(SidecarState {
             child: Mutex::new(None),
+            status: Mutex::new(ServerStatus::Stopped),
+            generation: Mutex::new(0),
         })
         .setup(|app| {
+            if let Err(e) = init_logging(app.handle()) {
+                eprintln!("Failed to initialize logging: {}", e);
+            }
+
             // Spawn the backend server as a sidecar process
-            let shell = app.shell();
-            
-            match shell.sidecar("streamdeck-server") {
-                Ok(command) => {
-                    match command.spawn() {
-                        Ok((mut rx, child)) => {
-                            // Store the child process handle
-                            let state = app.state::<SidecarState>();
-                            *state.child.lock().unwrap() = Some(child);
-                            
-                            // Spawn a task to handle sidecar output
-                            tauri::async_runtime::spawn(async move {
-                                use tauri_plugin_shell::process::CommandEvent;
-                                while let Some(event) = rx.recv().await {
-                                    match event {
-                                        CommandEvent::Stdout(line) => {
-                                            println!("[Backend] {}", String::from_utf8_lossy(&line));
-                                        }
-                                        CommandEvent::Stderr(line) => {
-                                            eprintln!("[Backend Error] {}", String::from_utf8_lossy(&line));
-                                        }
-                                        CommandEvent::Terminated(payload) => {
-                                            println!("[Backend] Process terminated with code: {:?}", payload.code);
-                                            break;
-                                        }
-                                        _ => {}
-                                    }
-                                }
-                            });
-                            
-                            println!("Backend server started successfully");
-                        }
-                        Err(e) => {
-                            eprintln!("Failed to spawn backend server: {}", e);
-                        }
-                    }
-                }
-                Err(e) => {
-                    eprintln!("Failed to create sidecar command: {}", e);
-                }
+            if let Err(e) = spawn_sidecar(app.handle().clone(), 0) {
+                error!("{}", e);
             }
-            
+
             Ok(())
         })
         .on_window_event(|window, event| {
             if let tauri::WindowEvent::Destroyed = event {
                 let child = {
                     let state = window.state::<SidecarState>();
+                    *state.generation.lock().unwrap() += 1;
                     state.child.lock().unwrap().take()
                 };
-            
+
                 if let Some(child) = child {
                     let _ = child.kill();
-                    println!("Backend server stopped");
+                    info!("Backend server stopped");
                 }
             }
-        })     
-        .invoke_handler(tauri::generate_handler![greet])
+        })
+        .invoke_handler(tauri::generate_handler![
+            greet,
+            start_server,
+            stop_server,
+            restart_server,
+            server_status,
+            send_to_backend
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }